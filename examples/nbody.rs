@@ -1,31 +1,31 @@
-use std::time::Duration;
-
-use bevy::{pbr::PointLightBundle, prelude::*};
-use bevy_poly_line::{PolyLine, PolyLineBundle, PolyLineMaterial, PolyLinePlugin};
+use bevy::prelude::*;
+use bevy_poly_line::{
+    AddPolyLineTrail, BatchedPolyLine, PolyLineMaterial, PolyLinePlugin, PolyLineTrail,
+    PolyLineTrailBundle,
+};
 
 use lazy_static::*;
 use rand::{prelude::*, Rng};
-use ringbuffer::{ConstGenericRingBuffer, RingBufferExt, RingBufferWrite};
 
 const NUM_BODIES: usize = 100;
 const TRAIL_LENGTH: usize = 128;
-const TRAIL_UPDATE_RATE_MILLIS: u64 = 25;
 
 fn main() {
     let mut app = App::build();
 
     app.insert_resource(ClearColor(Color::BLACK))
         .insert_resource(Msaa { samples: 4 })
-        .insert_resource(Timer::new(
-            Duration::from_millis(TRAIL_UPDATE_RATE_MILLIS),
-            true,
-        ))
         .insert_resource(Simulation {
             scale: 1e5,
             ..Default::default()
         })
         .add_plugins(DefaultPlugins)
-        .add_plugin(PolyLinePlugin)
+        // Batching merges trails that share a material into one draw call. Each body below
+        // gets its own randomly colored material, so here every trail is its own batch.
+        .add_plugin(PolyLinePlugin::batched())
+        // Sampled once per fixed simulation step rather than once per render frame, so the
+        // trail stays identical however many times a frame is resimulated.
+        .add_poly_line_trail::<TRAIL_LENGTH>(Simulation::default().timestep as f64)
         .add_startup_system(setup.system())
         .add_system(nbody_system.system())
         .add_system(rotator_system.system());
@@ -42,29 +42,28 @@ fn setup(mut commands: Commands, mut poly_line_materials: ResMut<Assets<PolyLine
             rng.gen_range(-100f32..100f32),
         );
         commands
-            .spawn_bundle((
-                Body {
-                    mass: 1_000.0,
-                    position,
+            .spawn_bundle((Body {
+                mass: 1_000.0,
+                position,
+                ..Default::default()
+            },))
+            .insert_bundle(PolyLineTrailBundle::<TRAIL_LENGTH> {
+                trail: PolyLineTrail::default(),
+                poly_line: bevy_poly_line::PolyLineBundle {
+                    material: poly_line_materials.add(PolyLineMaterial {
+                        width: 200.0,
+                        color: Color::rgb_linear(
+                            rng.gen_range(0.0..1.0),
+                            rng.gen_range(0.0..1.0),
+                            rng.gen_range(0.0..1.0),
+                        ),
+                        perspective: true,
+                        ..Default::default()
+                    }),
                     ..Default::default()
                 },
-                ConstGenericRingBuffer::<Vec3, TRAIL_LENGTH>::new(),
-            ))
-            .insert_bundle(PolyLineBundle {
-                poly_line: PolyLine {
-                    vertices: Vec::with_capacity(TRAIL_LENGTH),
-                },
-                material: poly_line_materials.add(PolyLineMaterial {
-                    width: 200.0,
-                    color: Color::rgb_linear(
-                        rng.gen_range(0.0..1.0),
-                        rng.gen_range(0.0..1.0),
-                        rng.gen_range(0.0..1.0),
-                    ),
-                    perspective: true,
-                }),
-                ..Default::default()
-            });
+            })
+            .insert(BatchedPolyLine);
     }
 
     // camera
@@ -98,20 +97,23 @@ struct Body {
 #[derive(Debug)]
 struct Simulation {
     pub accumulator: f32,
-    pub seconds_since_startup: f64,
     pub is_paused: bool,
     pub scale: f32,
     pub timestep: f32,
+    /// Number of fixed steps taken so far; doubles as the frame index passed to
+    /// [`PolyLineTrail::push_at`] so a trail sample is identified by simulation step rather
+    /// than by wall-clock time.
+    pub frame: u64,
 }
 
 impl Default for Simulation {
     fn default() -> Simulation {
         Simulation {
-            seconds_since_startup: 0.0,
             accumulator: 0.0,
             is_paused: false,
             scale: 5e4,
             timestep: 1. / 30.,
+            frame: 0,
         }
     }
 }
@@ -126,6 +128,7 @@ impl Simulation {
     fn step(&mut self) -> Option<f32> {
         if !self.is_paused && self.accumulator > self.timestep {
             self.accumulator -= self.timestep;
+            self.frame += 1;
             return Some(self.timestep * self.scale);
         }
         None
@@ -137,25 +140,19 @@ const EPSILON: f32 = 1.;
 
 fn nbody_system(
     time: Res<Time>,
-    mut timer: ResMut<Timer>,
     mut simulation: ResMut<Simulation>,
-    mut query: Query<(
-        Entity,
-        &mut Body,
-        &mut ConstGenericRingBuffer<Vec3, TRAIL_LENGTH>,
-        &mut PolyLine,
-    )>,
+    mut query: Query<(Entity, &mut Body, &mut PolyLineTrail<TRAIL_LENGTH>)>,
 ) {
     let mut bodies = query.iter_mut().collect::<Vec<_>>();
     // dbg!(&bodies);
 
     // Step simulation in fixed increments
-    simulation.update(&*time);
+    simulation.update(&time);
     while let Some(dt) = simulation.step() {
         // Start substeps
         for substep in 0..3 {
             // Clear accelerations and update positions
-            for (_, body, _, _) in bodies.iter_mut() {
+            for (_, body, _) in bodies.iter_mut() {
                 body.acceleration = Vec3::ZERO;
                 let dx = (*CS)[substep] * body.velocity * dt;
                 body.position += dx;
@@ -164,8 +161,8 @@ fn nbody_system(
             // Update accelerations
             for index1 in 0..bodies.len() {
                 let (bodies1, bodies2) = bodies.split_at_mut(index1 + 1);
-                let (_, body1, _, _) = &mut bodies1[index1];
-                for (_, body2, _, _) in bodies2.iter_mut() {
+                let (_, body1, _) = &mut bodies1[index1];
+                for (_, body2, _) in bodies2.iter_mut() {
                     let offset = body2.position - body1.position;
                     let distance_squared = offset.length_squared();
                     let normalized_offset = offset / distance_squared.sqrt();
@@ -177,7 +174,7 @@ fn nbody_system(
             }
 
             // Update velocities
-            for (_, body, _, _) in bodies.iter_mut() {
+            for (_, body, _) in bodies.iter_mut() {
                 let dv = (*DS)[substep] * body.acceleration * dt;
                 body.velocity += dv;
                 if substep == 2 {
@@ -186,17 +183,12 @@ fn nbody_system(
                 }
             }
         }
-    }
 
-    // Update Trails
-    timer.tick(time.delta());
-    if timer.just_finished() {
-        bodies
-            .iter_mut()
-            .for_each(|(_entity, body, trail, poly_line)| {
-                trail.push(body.position);
-                poly_line.vertices = trail.to_vec();
-            });
+        // Record one trail sample per fixed step, keyed by the step index rather than
+        // wall-clock time, so resimulating a step never leaves a duplicate point behind.
+        bodies.iter_mut().for_each(|(_entity, body, trail)| {
+            trail.push_at(simulation.frame, body.position);
+        });
     }
 }
 
@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        pipeline::{PrimitiveTopology, RenderPipeline, RenderPipelines},
+        render_graph::base::MainPass,
+    },
+};
+
+use crate::{
+    material::{Cap, Join, PolyLineMaterial},
+    polyline::{poly_line_vertex_data, PolyLine, PolyLineVertexData},
+};
+
+/// Opts a [`PolyLine`] entity into batched rendering (see [`crate::PolyLinePlugin::batched`]).
+///
+/// Whenever a member changes or is removed, [`poly_line_batch_system`] merges the vertices of all `BatchedPolyLine`
+/// entities that share a `Handle<PolyLineMaterial>` into one mesh and draws them with a
+/// single draw call, instead of the one draw call per entity `PolyLineBundle` normally
+/// costs. This suits simulations with hundreds of simultaneous, visually-similar trails
+/// (e.g. an n-body demo) where per-entity control over draw order doesn't matter.
+///
+/// A `BatchedPolyLine` entity still needs the rest of [`crate::PolyLineBundle`]'s
+/// components (`PolyLine`, `Handle<PolyLineMaterial>`, `GlobalTransform`) for its data to
+/// be read, but it is not itself drawn — [`poly_line_batch_system`] spawns one separate
+/// entity per material to carry the merged mesh.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BatchedPolyLine;
+
+/// Marker on the synthetic, per-material entity that [`poly_line_batch_system`] draws a
+/// batch's merged mesh through.
+pub(crate) struct PolyLineBatchOutput {
+    material: Handle<PolyLineMaterial>,
+}
+
+/// Components spawned for a batch's output entity; mirrors the drawable half of
+/// [`crate::PolyLineBundle`], minus the `PolyLine` itself (the merged mesh replaces it).
+#[derive(Bundle)]
+struct PolyLineBatchOutputBundle {
+    output: PolyLineBatchOutput,
+    material: Handle<PolyLineMaterial>,
+    mesh: Handle<Mesh>,
+    main_pass: MainPass,
+    draw: Draw,
+    visible: Visible,
+    render_pipelines: RenderPipelines,
+    transform: Transform,
+    global_transform: GlobalTransform,
+}
+
+/// Merges every [`BatchedPolyLine`] entity sharing a material into one mesh per material,
+/// each drawn through its own synthetic output entity.
+///
+/// This re-merges all of a material's batch whenever any member's `PolyLine` changes,
+/// which is coarser than the per-entity rebuild [`crate::polyline::poly_line_mesh_system`]
+/// does, but it's exactly what decouples draw calls from entity count: every segment for a
+/// material ends up in one vertex/index buffer. World-space positions are baked in at
+/// merge time (each source's `GlobalTransform` is applied to its vertices), since the
+/// output entity's own transform is shared by everything in the batch.
+///
+/// Only a changed member's ribbon geometry (the expensive `poly_line_vertex_data` work) is
+/// actually recomputed; every other member's is read back from `vertex_cache`, keyed by
+/// entity. Without this, a single changed trail in a many-trail batch would otherwise force
+/// every other member's geometry to be rebuilt from scratch too, just to re-concatenate
+/// them all into one mesh — exactly the per-frame cost batching was meant to avoid.
+///
+/// A member that's despawned (or loses its `PolyLine`/`BatchedPolyLine`) can't be read back
+/// off `sources` to find out which material it belonged to, so `membership` remembers that
+/// mapping from the previous run. Losing a member is treated the same as that member
+/// changing: its material is folded into `dirty_materials` so the batch still gets
+/// re-merged (without the removed entity's segments) even if no other member in it mutates
+/// this tick — otherwise the synthetic output entity would keep drawing a stale mesh that
+/// still contains the removed entity's geometry, possibly forever.
+#[allow(clippy::too_many_arguments)]
+pub fn poly_line_batch_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    materials: Res<Assets<PolyLineMaterial>>,
+    mut vertex_cache: Local<HashMap<Entity, PolyLineVertexData>>,
+    mut membership: Local<HashMap<Entity, Handle<PolyLineMaterial>>>,
+    removed: RemovedComponents<PolyLine>,
+    removed_batched: RemovedComponents<BatchedPolyLine>,
+    changed: Query<
+        (Entity, &Handle<PolyLineMaterial>),
+        (With<BatchedPolyLine>, With<PolyLine>, Changed<PolyLine>),
+    >,
+    sources: Query<
+        (Entity, &PolyLine, &GlobalTransform, &Handle<PolyLineMaterial>),
+        With<BatchedPolyLine>,
+    >,
+    mut outputs: Query<(Entity, &PolyLineBatchOutput, &mut Handle<Mesh>)>,
+) {
+    let mut dirty_materials: std::collections::HashSet<_> = std::collections::HashSet::new();
+    for entity in removed.iter().chain(removed_batched.iter()) {
+        vertex_cache.remove(&entity);
+        if let Some(material) = membership.remove(&entity) {
+            dirty_materials.insert(material);
+        }
+    }
+
+    let dirty_entities: std::collections::HashSet<_> = changed.iter().map(|(e, _)| e).collect();
+    dirty_materials.extend(changed.iter().map(|(_, material)| material.clone()));
+
+    membership.clear();
+    for (entity, _, _, material_handle) in sources.iter() {
+        membership.insert(entity, material_handle.clone());
+    }
+
+    if dirty_materials.is_empty() {
+        return;
+    }
+
+    // Seed every dirty material with an empty member list up front, so a material whose
+    // last remaining member was just removed still gets re-merged into an empty mesh
+    // instead of being skipped because `sources` no longer has anything to group.
+    let mut grouped: HashMap<Handle<PolyLineMaterial>, Vec<Entity>> = dirty_materials
+        .iter()
+        .map(|material| (material.clone(), Vec::new()))
+        .collect();
+    for (entity, _, _, material_handle) in sources.iter() {
+        if let Some(members) = grouped.get_mut(material_handle) {
+            members.push(entity);
+        }
+    }
+
+    for (material_handle, member_entities) in grouped {
+        let material = materials.get(&material_handle);
+        let width = material.map(|m| m.width).unwrap_or(1.0);
+        let color = material.map(|m| m.color).unwrap_or(Color::WHITE);
+        let join = material.map(|m| m.join).unwrap_or(Join::Miter);
+        let cap = material.map(|m| m.cap).unwrap_or(Cap::Butt);
+
+        for &entity in &member_entities {
+            if dirty_entities.contains(&entity) || !vertex_cache.contains_key(&entity) {
+                let (_, poly_line, _, _) = sources.get(entity).unwrap();
+                let data = poly_line_vertex_data(poly_line, width, color, join, cap);
+                vertex_cache.insert(entity, data);
+            }
+        }
+
+        let members = member_entities
+            .iter()
+            .map(|entity| {
+                let (_, _, transform, _) = sources.get(*entity).unwrap();
+                (vertex_cache.get(entity).unwrap(), transform)
+            })
+            .collect();
+
+        let mesh = merge_poly_lines(members);
+        let mesh_handle = meshes.add(mesh);
+
+        if let Some((_, _, mut existing)) = outputs
+            .iter_mut()
+            .find(|(_, output, _)| output.material == material_handle)
+        {
+            *existing = mesh_handle;
+        } else {
+            commands.spawn_bundle(PolyLineBatchOutputBundle {
+                output: PolyLineBatchOutput {
+                    material: material_handle.clone(),
+                },
+                material: material_handle,
+                mesh: mesh_handle,
+                main_pass: MainPass,
+                draw: Draw::default(),
+                visible: Visible {
+                    is_transparent: true,
+                    ..Default::default()
+                },
+                render_pipelines: RenderPipelines::from_pipelines(vec![RenderPipeline::new(
+                    crate::material::POLY_LINE_PIPELINE_HANDLE.typed(),
+                )]),
+                transform: Transform::default(),
+                global_transform: GlobalTransform::default(),
+            });
+        }
+    }
+}
+
+fn merge_poly_lines(members: Vec<(&PolyLineVertexData, &GlobalTransform)>) -> Mesh {
+    let mut positions = Vec::new();
+    let mut prevs = Vec::new();
+    let mut nexts = Vec::new();
+    let mut sides = Vec::new();
+    let mut colors = Vec::new();
+    let mut widths = Vec::new();
+    let mut joins = Vec::new();
+    let mut cap_extends = Vec::new();
+    let mut indices = Vec::new();
+
+    for (data, transform) in members {
+        let base = positions.len() as u32;
+
+        let to_world = |p: [f32; 3]| {
+            let v = transform.mul_vec3(Vec3::from(p));
+            [v.x, v.y, v.z]
+        };
+        positions.extend(data.positions.iter().copied().map(to_world));
+        prevs.extend(data.prevs.iter().copied().map(to_world));
+        nexts.extend(data.nexts.iter().copied().map(to_world));
+        sides.extend(data.sides.iter().copied());
+        colors.extend(data.colors.iter().copied());
+        widths.extend(data.widths.iter().copied());
+        joins.extend(data.joins.iter().copied());
+        cap_extends.extend(data.cap_extends.iter().copied());
+        indices.extend(data.indices.iter().copied().map(|i| i + base));
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        VertexAttributeValues::Float3(positions),
+    );
+    mesh.set_attribute("Vertex_Prev", VertexAttributeValues::Float3(prevs));
+    mesh.set_attribute("Vertex_Next", VertexAttributeValues::Float3(nexts));
+    mesh.set_attribute("Vertex_Side", VertexAttributeValues::Float(sides));
+    mesh.set_attribute("Vertex_Join", VertexAttributeValues::Float(joins));
+    mesh.set_attribute(
+        "Vertex_CapExtend",
+        VertexAttributeValues::Float(cap_extends),
+    );
+    // The batch always carries color/width attributes, since members are free to mix
+    // per-vertex overrides with flat ones; members without their own colors/widths were
+    // already filled in with the material's flat fallback by `poly_line_vertex_data`.
+    mesh.set_attribute("Vertex_Color", VertexAttributeValues::Float4(colors));
+    mesh.set_attribute("Vertex_Width", VertexAttributeValues::Float(widths));
+    mesh.set_indices(Some(Indices::U32(indices)));
+
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::polyline::PolyLineBundle;
+    use bevy::{app::App, asset::AssetPlugin, core::CorePlugin};
+
+    fn test_app() -> App {
+        let mut builder = App::build();
+        builder.add_plugin(CorePlugin);
+        builder.add_plugin(AssetPlugin);
+        builder.add_asset::<Mesh>();
+        builder.add_asset::<PolyLineMaterial>();
+        builder.add_system_to_stage(CoreStage::PostUpdate, poly_line_batch_system.system());
+        builder.app
+    }
+
+    fn merged_vertex_count(app: &mut App, output_material: &Handle<PolyLineMaterial>) -> usize {
+        let mesh_handle = {
+            let world = &mut app.world;
+            let mut query = world.query::<(&PolyLineBatchOutput, &Handle<Mesh>)>();
+            query
+                .iter(world)
+                .find(|(output, _)| output.material == *output_material)
+                .map(|(_, mesh)| mesh.clone())
+                .expect("batch output entity for this material")
+        };
+        let meshes = app.world.get_resource::<Assets<Mesh>>().unwrap();
+        let mesh = meshes.get(&mesh_handle).unwrap();
+        match mesh.attribute(Mesh::ATTRIBUTE_POSITION).unwrap() {
+            VertexAttributeValues::Float3(positions) => positions.len(),
+            _ => panic!("unexpected position attribute format"),
+        }
+    }
+
+    fn two_point_line(x: f32) -> PolyLine {
+        PolyLine {
+            vertices: vec![Vec3::new(x, 0.0, 0.0), Vec3::new(x, 1.0, 0.0)],
+            ..Default::default()
+        }
+    }
+
+    /// Adding two members, then despawning one without touching the other, should leave the
+    /// merged mesh containing only the remaining member's geometry — not the stale geometry
+    /// of the despawned one.
+    #[test]
+    fn despawning_a_member_drops_its_geometry_from_the_merged_mesh() {
+        let mut app = test_app();
+        let material_handle = {
+            let mut materials = app
+                .world
+                .get_resource_mut::<Assets<PolyLineMaterial>>()
+                .unwrap();
+            materials.add(PolyLineMaterial::default())
+        };
+
+        let kept = app
+            .world
+            .spawn()
+            .insert_bundle(PolyLineBundle {
+                poly_line: two_point_line(0.0),
+                material: material_handle.clone(),
+                ..Default::default()
+            })
+            .insert(BatchedPolyLine)
+            .id();
+        let removed = app
+            .world
+            .spawn()
+            .insert_bundle(PolyLineBundle {
+                poly_line: two_point_line(1.0),
+                material: material_handle.clone(),
+                ..Default::default()
+            })
+            .insert(BatchedPolyLine)
+            .id();
+
+        app.update();
+        // Both members' ribbon geometry (2 vertices per side, 4 per segment) should be merged.
+        assert_eq!(merged_vertex_count(&mut app, &material_handle), 8);
+
+        app.world.entity_mut(removed).despawn();
+        app.update();
+
+        assert_eq!(merged_vertex_count(&mut app, &material_handle), 4);
+
+        // The surviving member should still be intact (not just an empty batch).
+        let poly_line = app.world.get::<PolyLine>(kept).unwrap();
+        assert_eq!(poly_line.vertices.len(), 2);
+    }
+}
@@ -0,0 +1,133 @@
+//! A plugin for rendering long, multi-segment poly-lines in bevy.
+//!
+//! A [`PolyLine`] is an ordered list of vertices that gets expanded into a ribbon of
+//! triangles whenever it changes, which makes it cheap to draw trails, comet tails, orbit paths,
+//! and other "connect the dots" effects as a single draw call instead of one entity per
+//! segment.
+
+// Bevy's ECS query types are inherently nested generics, and its `#[derive(Bundle)]`
+// expands to a `std::mem::forget` over each field regardless of whether that field type
+// happens to implement `Drop` (several of `bevy_render`'s bundle components don't) — both
+// lints fire squarely inside Bevy 0.5's own derive output, not this crate's code.
+#![allow(clippy::type_complexity)]
+#![allow(clippy::forget_non_drop)]
+
+mod batch;
+mod material;
+mod polyline;
+mod trail;
+
+pub use batch::BatchedPolyLine;
+pub use material::{Cap, Join, PolyLineMaterial};
+pub use polyline::{PolyLine, PolyLineBundle};
+pub use trail::{poly_line_trail_system, PolyLineTrail, PolyLineTrailBundle, PolyLineTrailSnapshot};
+
+use bevy::{
+    asset::Assets,
+    core::FixedTimestep,
+    ecs::prelude::*,
+    prelude::*,
+    render::{
+        pipeline::PipelineDescriptor,
+        render_graph::RenderGraph,
+        shader::{self, Shader},
+    },
+};
+
+/// Labels for ordering this crate's systems relative to each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, SystemLabel)]
+pub enum PolyLineSystem {
+    /// [`polyline::poly_line_mesh_system`], which expands a [`PolyLine`]'s vertices into
+    /// its mesh.
+    MeshUpdate,
+    /// [`batch::poly_line_batch_system`], which merges [`BatchedPolyLine`] entities sharing
+    /// a material into one draw call.
+    BatchUpdate,
+}
+
+/// Adds poly-line rendering support to an [`App`].
+///
+/// Registers the [`PolyLineMaterial`] asset and its render pipeline, and runs the system
+/// that expands each changed [`PolyLine`]'s vertices into its mesh.
+///
+/// Batched rendering (merging every [`BatchedPolyLine`] entity sharing a material into one
+/// draw call) is opt-in, since it spawns an extra entity per material and delays individual
+/// [`PolyLine`] updates by re-deriving the merged mesh from scratch; enable it with
+/// [`PolyLinePlugin::batched`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PolyLinePlugin {
+    batched: bool,
+}
+
+impl PolyLinePlugin {
+    /// Also runs [`batch::poly_line_batch_system`], so [`BatchedPolyLine`] entities are
+    /// merged by material into one draw call instead of drawing individually.
+    pub fn batched() -> Self {
+        PolyLinePlugin { batched: true }
+    }
+}
+
+impl Plugin for PolyLinePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_asset::<PolyLineMaterial>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                shader::asset_shader_defs_system::<PolyLineMaterial>.system(),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                polyline::poly_line_mesh_system
+                    .system()
+                    .label(PolyLineSystem::MeshUpdate),
+            );
+
+        if self.batched {
+            app.add_system_to_stage(
+                CoreStage::PostUpdate,
+                batch::poly_line_batch_system
+                    .system()
+                    .label(PolyLineSystem::BatchUpdate)
+                    .after(PolyLineSystem::MeshUpdate),
+            );
+        }
+
+        let world = app.world_mut().cell();
+        let mut pipelines = world
+            .get_resource_mut::<Assets<PipelineDescriptor>>()
+            .unwrap();
+        let mut shaders = world.get_resource_mut::<Assets<Shader>>().unwrap();
+        let mut render_graph = world.get_resource_mut::<RenderGraph>().unwrap();
+
+        material::add_poly_line_graph(&mut pipelines, &mut shaders, &mut render_graph);
+    }
+}
+
+/// Registers the update system for one [`PolyLineTrail`] capacity.
+///
+/// [`PolyLineTrail`] is generic over its ring-buffer capacity `N`, so [`PolyLinePlugin`]
+/// can't register its system for every capacity an app might use up front. Call this once
+/// per distinct `N`, alongside `.add_plugin(PolyLinePlugin::default())`.
+///
+/// `step` is the duration, in seconds, of one [`PolyLineTrail::push_at`] frame; the mirroring
+/// system runs on a [`FixedTimestep`] of this length rather than once per render frame, so a
+/// trail stays in lockstep with the fixed-timestep simulation driving it (and, for a
+/// rollback-netcode entity, with however many frames a resimulation replays) instead of
+/// drifting with the display's frame rate.
+pub trait AddPolyLineTrail {
+    fn add_poly_line_trail<const N: usize>(&mut self, step: f64) -> &mut Self;
+}
+
+impl AddPolyLineTrail for AppBuilder {
+    fn add_poly_line_trail<const N: usize>(&mut self, step: f64) -> &mut Self {
+        self.add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .with_run_criteria(FixedTimestep::step(step))
+                .with_system(
+                    poly_line_trail_system::<N>
+                        .system()
+                        .before(PolyLineSystem::MeshUpdate),
+                ),
+        )
+    }
+}
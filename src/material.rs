@@ -0,0 +1,166 @@
+use bevy::{
+    asset::{Assets, HandleUntyped},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        pipeline::{
+            BlendFactor, BlendOperation, BlendState, ColorWrite, DepthStencilState,
+            PipelineDescriptor,
+        },
+        render_graph::{base, AssetRenderResourcesNode, RenderGraph},
+        renderer::RenderResources,
+        shader::{Shader, ShaderDefs, ShaderStage, ShaderStages},
+    },
+};
+
+/// The handle under which the poly-line render pipeline is registered.
+pub const POLY_LINE_PIPELINE_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(PipelineDescriptor::TYPE_UUID, 0x8aba_19c3_1e5a_4c61);
+
+/// The material used to draw a [`crate::PolyLine`].
+///
+/// `color` is the fallback used for any vertex that isn't covered by
+/// [`crate::PolyLine::colors`]; a `PolyLine` with per-vertex colors ignores `color` entirely.
+/// `width` is always used, scaled at each vertex by [`crate::PolyLine::widths`] when set.
+#[derive(Debug, RenderResources, ShaderDefs, TypeUuid)]
+#[uuid = "0b2f4a3e-9e9a-4f2a-9f6e-6a6e7f9a2b1d"]
+pub struct PolyLineMaterial {
+    /// Width of the line, as a screen-space offset in normalized device coordinates. When
+    /// `perspective` is true it is first divided by each vertex's clip-space `w`.
+    pub width: f32,
+    /// Flat color used wherever the owning [`crate::PolyLine`] has no per-vertex colors.
+    ///
+    /// Baked into `Vertex_Color` per-vertex by `poly_line_vertex_data` rather than read by
+    /// the shader as a uniform, so it's ignored here to avoid binding a resource nothing
+    /// on the GPU side ever reads.
+    #[render_resources(ignore)]
+    pub color: Color,
+    /// When true, `width` is divided by the vertex's clip-space `w`, so the line gets
+    /// narrower on screen as it recedes from the camera, like world geometry.
+    /// When false, `width` is a constant on-screen width regardless of distance.
+    ///
+    /// `bool` isn't a uniform-buffer-compatible [`RenderResource`], so this is wired into
+    /// the shader as a `POLYLINEMATERIAL_PERSPECTIVE` def (toggling an `#ifdef` branch)
+    /// instead of a bound value, the same way `bevy_pbr`'s `StandardMaterial` handles its
+    /// own `bool` fields.
+    #[render_resources(ignore)]
+    #[shader_def]
+    pub perspective: bool,
+    /// How consecutive segments are connected at interior vertices.
+    ///
+    /// This only decides what geometry [`crate::polyline::poly_line_mesh_system`] bakes
+    /// into the mesh; it isn't read by the shader, so changing it after a `PolyLine` was
+    /// last rebuilt has no effect until that `PolyLine`'s vertices change again.
+    #[render_resources(ignore)]
+    pub join: Join,
+    /// How far a `Join::Miter` corner may extend before it is flattened to a bevel,
+    /// expressed as a multiple of the half-width. A corner's miter length grows without
+    /// bound as the angle between segments approaches zero, so this protects against
+    /// spikes on sharp switchbacks.
+    pub miter_limit: f32,
+    /// How the two open ends of the line are finished.
+    ///
+    /// Like `join`, this only affects the mesh baked by
+    /// [`crate::polyline::poly_line_mesh_system`].
+    #[render_resources(ignore)]
+    pub cap: Cap,
+}
+
+impl Default for PolyLineMaterial {
+    fn default() -> Self {
+        PolyLineMaterial {
+            width: 1.0,
+            color: Color::WHITE,
+            perspective: false,
+            join: Join::Miter,
+            miter_limit: 4.0,
+            cap: Cap::Butt,
+        }
+    }
+}
+
+/// How two adjacent segments of a [`crate::PolyLine`] are connected at a shared vertex.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Join {
+    /// Extend both segment edges until they intersect, falling back to a flat [`Join::Bevel`]
+    /// cut once that intersection would pass `PolyLineMaterial::miter_limit`.
+    Miter,
+    /// Cut the corner flat with a single edge between the two segments' outer offsets.
+    ///
+    /// Simplified from a "true" bevel: rather than building two distinct offset points (one
+    /// per segment's own normal) joined by an extra triangle, this reuses the single shared
+    /// vertex offset by the outgoing segment's normal (`n1`) that `Join::Miter`'s
+    /// miter-limit fallback also uses. The corner is still watertight — there's no gap or
+    /// tear — but its shape is a biased approximation of a real bevel rather than the
+    /// textbook two-vertex cut.
+    Bevel,
+    /// Fill the corner with a disc of radius `width / 2`, avoiding gaps at any angle.
+    Round,
+}
+
+/// How the start and end of a [`crate::PolyLine`] are finished.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cap {
+    /// Stop exactly at the endpoint vertex.
+    Butt,
+    /// Extend past the endpoint by `width / 2`, as if the line continued one more half-step.
+    Square,
+    /// Cap the endpoint with a disc of radius `width / 2`.
+    Round,
+}
+
+pub(crate) fn add_poly_line_graph(
+    pipelines: &mut Assets<PipelineDescriptor>,
+    shaders: &mut Assets<Shader>,
+    render_graph: &mut RenderGraph,
+) {
+    let pipeline = build_poly_line_pipeline(shaders);
+    pipelines.set_untracked(POLY_LINE_PIPELINE_HANDLE, pipeline);
+
+    render_graph.add_system_node(
+        "poly_line_material",
+        AssetRenderResourcesNode::<PolyLineMaterial>::new(true),
+    );
+    render_graph
+        .add_node_edge("poly_line_material", base::node::MAIN_PASS)
+        .unwrap();
+}
+
+fn build_poly_line_pipeline(shaders: &mut Assets<Shader>) -> PipelineDescriptor {
+    let mut descriptor = PipelineDescriptor::default_config(ShaderStages {
+        vertex: shaders.add(Shader::from_glsl(
+            ShaderStage::Vertex,
+            include_str!("shaders/polyline.vert"),
+        )),
+        fragment: Some(shaders.add(Shader::from_glsl(
+            ShaderStage::Fragment,
+            include_str!("shaders/polyline.frag"),
+        ))),
+    });
+
+    // Trails fade in and out via per-vertex alpha, so the pipeline needs straight alpha
+    // blending rather than the opaque defaults.
+    // `default_config` always populates `depth_stencil`.
+    descriptor.depth_stencil = Some(DepthStencilState {
+        depth_write_enabled: false,
+        ..descriptor.depth_stencil.unwrap()
+    });
+    if let Some(target) = descriptor
+        .color_target_states
+        .get_mut(0)
+    {
+        target.color_blend = BlendState {
+            src_factor: BlendFactor::SrcAlpha,
+            dst_factor: BlendFactor::OneMinusSrcAlpha,
+            operation: BlendOperation::Add,
+        };
+        target.alpha_blend = BlendState {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::OneMinusSrcAlpha,
+            operation: BlendOperation::Add,
+        };
+        target.write_mask = ColorWrite::ALL;
+    }
+
+    descriptor
+}
@@ -0,0 +1,410 @@
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        pipeline::{PrimitiveTopology, RenderPipeline, RenderPipelines},
+        render_graph::base::MainPass,
+    },
+};
+
+use crate::{
+    batch::BatchedPolyLine,
+    material::{Cap, Join, PolyLineMaterial},
+};
+
+/// Segments used to approximate a `Join::Round` or `Cap::Round` disc.
+const ROUND_FAN_SEGMENTS: usize = 12;
+
+/// A polyline, defined by an ordered list of vertices.
+///
+/// Each consecutive pair of vertices is expanded into a quad by
+/// [`poly_line_mesh_system`], so a `PolyLine` with `n` vertices draws `n - 1` segments.
+/// An empty `PolyLine` draws nothing; a single-vertex one draws no segments, only the
+/// disc of a `Cap::Round` when its material uses one.
+#[derive(Debug, Default, Clone)]
+pub struct PolyLine {
+    /// Vertex positions, in the order they should be connected.
+    pub vertices: Vec<Vec3>,
+    /// Optional per-vertex color, parallel to `vertices`.
+    ///
+    /// When empty, every vertex falls back to the flat [`PolyLineMaterial::color`].
+    /// When non-empty it must be the same length as `vertices`; colors are linearly
+    /// interpolated across each segment, which is what lets a trail fade from a bright
+    /// head to a transparent tail instead of requiring one material per segment.
+    pub colors: Vec<Color>,
+    /// Optional per-vertex width multiplier, parallel to `vertices`.
+    ///
+    /// Each value scales [`PolyLineMaterial::width`] at that vertex, and is linearly
+    /// interpolated across each segment. When empty, every vertex uses a multiplier of
+    /// `1.0`, i.e. the line is a constant `width`. This is what lets a comet or ship trail
+    /// taper from thick at the head to a point at the tail.
+    pub widths: Vec<f32>,
+    /// Logical index of the oldest vertex in `vertices` (and `colors`/`widths`, if set).
+    ///
+    /// Vertex `i` of the line is read from `vertices[(ring_start + i) % vertices.len()]`,
+    /// wrapping around. This lets a fixed-capacity ring buffer like [`crate::PolyLineTrail`]
+    /// overwrite its single oldest slot in place and bump `ring_start` instead of
+    /// reshuffling every vertex to keep index `0` as the oldest point. Defaults to `0`,
+    /// which is a plain, already-ordered `Vec`.
+    pub ring_start: usize,
+}
+
+impl PolyLine {
+    fn logical_index(&self, i: usize) -> usize {
+        if self.vertices.is_empty() {
+            0
+        } else {
+            (self.ring_start + i) % self.vertices.len()
+        }
+    }
+}
+
+/// Components required to draw a [`PolyLine`].
+///
+/// Unlike [`MeshBundle`], the `mesh` handle is populated and kept up to date by
+/// [`poly_line_mesh_system`] rather than being provided by the caller.
+#[derive(Bundle)]
+pub struct PolyLineBundle {
+    pub poly_line: PolyLine,
+    pub material: Handle<PolyLineMaterial>,
+    pub mesh: Handle<Mesh>,
+    pub main_pass: MainPass,
+    pub draw: Draw,
+    pub visible: Visible,
+    pub render_pipelines: RenderPipelines,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+}
+
+impl Default for PolyLineBundle {
+    fn default() -> Self {
+        PolyLineBundle {
+            poly_line: PolyLine::default(),
+            material: Handle::default(),
+            mesh: Handle::default(),
+            main_pass: MainPass,
+            draw: Draw::default(),
+            visible: Visible {
+                is_transparent: true,
+                ..Default::default()
+            },
+            render_pipelines: RenderPipelines::from_pipelines(vec![RenderPipeline::new(
+                crate::material::POLY_LINE_PIPELINE_HANDLE.typed(),
+            )]),
+            transform: Transform::default(),
+            global_transform: GlobalTransform::default(),
+        }
+    }
+}
+
+/// Expands every [`PolyLine`]'s vertices (and optional per-vertex colors and widths) into
+/// the quad strip mesh that gets rasterized as the line's ribbon of segments.
+///
+/// Each source vertex becomes two mesh vertices, one per side of the ribbon. The actual
+/// screen-space offset is computed in the vertex shader (see `polyline.vert`) so that
+/// perspective-correct width is resolved per-pixel rather than baked into world space here;
+/// this system only supplies each side vertex with the neighboring positions it needs to
+/// derive its segment tangent, plus the [`Join`]/[`Cap`] geometry read from the `PolyLine`'s
+/// material.
+///
+/// Note this only re-bakes the mesh when the `PolyLine` itself changes; editing
+/// `PolyLineMaterial::join` or `::cap` on an otherwise-unchanged `PolyLine` won't be
+/// reflected until its vertices are touched again.
+///
+/// Skips [`BatchedPolyLine`] entities: those are drawn through the merged mesh
+/// [`crate::batch::poly_line_batch_system`] builds instead.
+///
+/// Once a `PolyLine` already has a mesh (e.g. a [`crate::PolyLineTrail`] ticking every
+/// fixed step), this writes its new vertex data into that existing [`Mesh`] asset in place
+/// with [`Assets::get_mut`] instead of registering a brand-new one with `meshes.add` every
+/// time. That avoids leaking a fresh `Handle<Mesh>` (and the `Mesh` it pointed at) into
+/// `Assets<Mesh>` on every change; it does not, however, avoid Bevy 0.5's renderer
+/// re-uploading the whole vertex buffer to the GPU on any `Modified` asset event, since
+/// that renderer has no public API for a partial/sub-range buffer write.
+pub fn poly_line_mesh_system(
+    mut meshes: ResMut<Assets<Mesh>>,
+    materials: Res<Assets<PolyLineMaterial>>,
+    mut query: Query<
+        (&PolyLine, &Handle<PolyLineMaterial>, &mut Handle<Mesh>),
+        (Changed<PolyLine>, Without<BatchedPolyLine>),
+    >,
+) {
+    for (poly_line, material_handle, mut mesh_handle) in query.iter_mut() {
+        let material = materials.get(material_handle);
+        let width = material.map(|m| m.width).unwrap_or(1.0);
+        let color = material.map(|m| m.color).unwrap_or(Color::WHITE);
+        let join = material.map(|m| m.join).unwrap_or(Join::Miter);
+        let cap = material.map(|m| m.cap).unwrap_or(Cap::Butt);
+
+        if let Some(mesh) = meshes.get_mut(&*mesh_handle) {
+            write_poly_line_mesh(poly_line, width, color, join, cap, mesh);
+        } else {
+            let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+            write_poly_line_mesh(poly_line, width, color, join, cap, &mut mesh);
+            *mesh_handle = meshes.add(mesh);
+        }
+    }
+}
+
+/// The raw per-vertex buffers behind a [`PolyLine`]'s ribbon mesh, before they're handed to
+/// a `Mesh`. Pulled out of [`write_poly_line_mesh`] so [`crate::batch`] can concatenate
+/// several polylines' buffers into one mesh instead of building (and then re-reading) a
+/// `Mesh` per source line, and cache a member's data across batch rebuilds it wasn't the
+/// one that changed.
+#[derive(Clone)]
+pub(crate) struct PolyLineVertexData {
+    pub positions: Vec<[f32; 3]>,
+    pub prevs: Vec<[f32; 3]>,
+    pub nexts: Vec<[f32; 3]>,
+    pub sides: Vec<f32>,
+    pub colors: Vec<[f32; 4]>,
+    pub widths: Vec<f32>,
+    pub joins: Vec<f32>,
+    pub cap_extends: Vec<f32>,
+    pub indices: Vec<u32>,
+}
+
+pub(crate) fn poly_line_vertex_data(
+    poly_line: &PolyLine,
+    width: f32,
+    flat_color: Color,
+    join: Join,
+    cap: Cap,
+) -> PolyLineVertexData {
+    let vertex_count = poly_line.vertices.len();
+    let use_vertex_color = poly_line.colors.len() == vertex_count;
+    let use_vertex_width = poly_line.widths.len() == vertex_count;
+
+    let mut positions = Vec::with_capacity(vertex_count * 2);
+    let mut prevs = Vec::with_capacity(vertex_count * 2);
+    let mut nexts = Vec::with_capacity(vertex_count * 2);
+    let mut sides = Vec::with_capacity(vertex_count * 2);
+    let mut colors = Vec::with_capacity(vertex_count * 2);
+    let mut widths = Vec::with_capacity(vertex_count * 2);
+    let mut joins = Vec::with_capacity(vertex_count * 2);
+    let mut cap_extends = Vec::with_capacity(vertex_count * 2);
+    let mut indices = Vec::with_capacity(vertex_count.saturating_sub(1) * 6);
+    // Index (into `positions` etc.) of vertex `i`'s side-pair. A `Join::Round`/`Cap::Round`
+    // vertex also pushes a whole fan right after its pair, so the next vertex's pair does
+    // NOT start at a fixed `i * 2` offset; this is recorded per vertex instead of assumed.
+    let mut vertex_base = Vec::with_capacity(vertex_count);
+
+    for i in 0..vertex_count {
+        let position = poly_line.vertices[poly_line.logical_index(i)];
+        let is_endpoint = i == 0 || i == vertex_count - 1;
+        let prev = poly_line.vertices[poly_line.logical_index(i.saturating_sub(1))];
+        let next = poly_line.vertices[poly_line.logical_index((i + 1).min(vertex_count - 1))];
+        let color = if use_vertex_color {
+            poly_line.colors[poly_line.logical_index(i)]
+        } else {
+            flat_color
+        };
+        let width_mult = if use_vertex_width {
+            poly_line.widths[poly_line.logical_index(i)]
+        } else {
+            1.0
+        };
+        // Bevel is implemented as a per-vertex override rather than a material-level
+        // uniform: it tells the shader to flatten this particular joint instead of
+        // attempting a miter, which keeps the join decision as local as the color/width
+        // overrides above.
+        let join_flatten = if is_endpoint || join == Join::Miter {
+            0.0
+        } else {
+            1.0
+        };
+        let cap_extend = if is_endpoint && cap == Cap::Square {
+            1.0
+        } else {
+            0.0
+        };
+
+        vertex_base.push(positions.len() as u32);
+        for side in [-1.0_f32, 1.0_f32] {
+            positions.push([position.x, position.y, position.z]);
+            prevs.push([prev.x, prev.y, prev.z]);
+            nexts.push([next.x, next.y, next.z]);
+            sides.push(side);
+            colors.push(color.as_rgba_f32());
+            widths.push(width_mult);
+            joins.push(join_flatten);
+            cap_extends.push(cap_extend);
+        }
+
+        let is_interior = !is_endpoint;
+        if (is_interior && join == Join::Round) || (is_endpoint && cap == Cap::Round) {
+            push_round_fan(
+                position,
+                width * width_mult / 2.0,
+                color,
+                &mut positions,
+                &mut prevs,
+                &mut nexts,
+                &mut sides,
+                &mut colors,
+                &mut widths,
+                &mut joins,
+                &mut cap_extends,
+                &mut indices,
+            );
+        }
+    }
+
+    for segment in 0..vertex_count.saturating_sub(1) {
+        let base = vertex_base[segment];
+        let next_base = vertex_base[segment + 1];
+        indices.extend_from_slice(&[
+            base,
+            base + 1,
+            next_base,
+            base + 1,
+            next_base + 1,
+            next_base,
+        ]);
+    }
+
+    PolyLineVertexData {
+        positions,
+        prevs,
+        nexts,
+        sides,
+        colors,
+        widths,
+        joins,
+        cap_extends,
+        indices,
+    }
+}
+
+/// Rebuilds `mesh`'s attributes and indices from `poly_line`, in place.
+///
+/// Reusing an existing [`Mesh`] (rather than constructing a new one every call) is what
+/// lets [`poly_line_mesh_system`] update a `PolyLine` without registering a fresh
+/// `Handle<Mesh>` each time.
+fn write_poly_line_mesh(
+    poly_line: &PolyLine,
+    width: f32,
+    flat_color: Color,
+    join: Join,
+    cap: Cap,
+    mesh: &mut Mesh,
+) {
+    let data = poly_line_vertex_data(poly_line, width, flat_color, join, cap);
+
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        VertexAttributeValues::Float3(data.positions),
+    );
+    mesh.set_attribute("Vertex_Prev", VertexAttributeValues::Float3(data.prevs));
+    mesh.set_attribute("Vertex_Next", VertexAttributeValues::Float3(data.nexts));
+    mesh.set_attribute("Vertex_Side", VertexAttributeValues::Float(data.sides));
+    mesh.set_attribute("Vertex_Join", VertexAttributeValues::Float(data.joins));
+    mesh.set_attribute(
+        "Vertex_CapExtend",
+        VertexAttributeValues::Float(data.cap_extends),
+    );
+    mesh.set_attribute("Vertex_Color", VertexAttributeValues::Float4(data.colors));
+    mesh.set_attribute("Vertex_Width", VertexAttributeValues::Float(data.widths));
+    mesh.set_indices(Some(Indices::U32(data.indices)));
+}
+
+/// Appends a triangle fan approximating a disc of `radius` centered on `center`, used to
+/// fill `Join::Round` corners and `Cap::Round` endpoints. The fan's own vertices carry
+/// harmless placeholder values for the ribbon-only attributes (`side = 0` puts them at the
+/// disc's true radius rather than offset further by the vertex shader).
+///
+/// The fan is flat, facing `+Z` in the polyline's local space; this is a reasonable
+/// approximation for trails viewed roughly face-on (the common case for this crate), but
+/// unlike the rest of the ribbon it is not billboarded toward the camera or scaled by
+/// `PolyLineMaterial::perspective`, so it can look slightly off-axis under a grazing view
+/// angle. A billboarded version of this would need the camera transform, which isn't
+/// available in this mesh-building system.
+#[allow(clippy::too_many_arguments)]
+fn push_round_fan(
+    center: Vec3,
+    radius: f32,
+    color: Color,
+    positions: &mut Vec<[f32; 3]>,
+    prevs: &mut Vec<[f32; 3]>,
+    nexts: &mut Vec<[f32; 3]>,
+    sides: &mut Vec<f32>,
+    colors: &mut Vec<[f32; 4]>,
+    widths: &mut Vec<f32>,
+    joins: &mut Vec<f32>,
+    cap_extends: &mut Vec<f32>,
+    indices: &mut Vec<u32>,
+) {
+    let base = positions.len() as u32;
+
+    let mut push_vertex = |position: Vec3| {
+        positions.push([position.x, position.y, position.z]);
+        prevs.push([center.x, center.y, center.z]);
+        nexts.push([center.x, center.y, center.z]);
+        sides.push(0.0);
+        colors.push(color.as_rgba_f32());
+        widths.push(1.0);
+        joins.push(0.0);
+        cap_extends.push(0.0);
+    };
+
+    push_vertex(center);
+
+    for step in 0..=ROUND_FAN_SEGMENTS {
+        let angle = (step as f32 / ROUND_FAN_SEGMENTS as f32) * std::f32::consts::TAU;
+        let offset = Vec3::new(angle.cos(), angle.sin(), 0.0) * radius;
+        push_vertex(center + offset);
+    }
+
+    for step in 0..ROUND_FAN_SEGMENTS as u32 {
+        indices.extend_from_slice(&[base, base + 1 + step, base + 2 + step]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `Join::Round` vertex interleaves a whole fan of extra vertices right after its
+    /// side-pair, so every later vertex's side-pair starts later in `positions` than a flat
+    /// `i * 2` stride would predict. The segment after the round vertex must reference that
+    /// shifted offset, not the naive stride.
+    #[test]
+    fn segment_indices_skip_interleaved_round_fan() {
+        let poly_line = PolyLine {
+            vertices: vec![
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(2.0, 0.0, 0.0),
+            ],
+            ..Default::default()
+        };
+
+        let data = poly_line_vertex_data(&poly_line, 1.0, Color::WHITE, Join::Round, Cap::Butt);
+
+        // Vertex 0 (an endpoint) is a plain side-pair at [0, 1].
+        // Vertex 1 (interior, so it gets a round fan) is a plain side-pair at [2, 3],
+        // immediately followed by its fan: 1 center vertex + (ROUND_FAN_SEGMENTS + 1) rim
+        // vertices, landing vertex 2's side-pair at 4 + (ROUND_FAN_SEGMENTS + 2).
+        let vertex_2_base = 4 + ROUND_FAN_SEGMENTS as u32 + 2;
+
+        assert_eq!(
+            data.positions[vertex_2_base as usize],
+            [
+                poly_line.vertices[2].x,
+                poly_line.vertices[2].y,
+                poly_line.vertices[2].z
+            ],
+            "vertex 2's side-pair should start after vertex 1's interleaved round fan"
+        );
+
+        // Segment index quads are appended after all per-vertex fan triangles, in segment
+        // order, so the last 6 indices are segment 1 (connecting source vertices 1 and 2).
+        let segment_1 = &data.indices[data.indices.len() - 6..];
+        assert_eq!(
+            segment_1,
+            // Must reference vertex 2's actual offset, not `1 * 2 + 2 = 4`, which would
+            // land on the round fan's own center vertex.
+            &[2, 3, vertex_2_base, 3, vertex_2_base + 1, vertex_2_base],
+        );
+    }
+}
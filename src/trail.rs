@@ -0,0 +1,250 @@
+use bevy::prelude::*;
+
+use crate::{PolyLine, PolyLineBundle};
+
+/// A fixed-length trail of points behind an entity, backed by a ring buffer of capacity `N`.
+///
+/// Pair it with a [`PolyLine`] (see [`PolyLineTrailBundle`]) and call [`PolyLineTrail::push_at`]
+/// once per simulation frame; [`poly_line_trail_system`] keeps the `PolyLine` in sync by
+/// writing the single newly-pushed point into its already-allocated `vertices` buffer and
+/// advancing [`PolyLine::ring_start`], instead of reallocating and copying the whole trail
+/// every tick the way `poly_line.vertices = ring_buffer.to_vec()` would.
+///
+/// Samples are indexed by an explicit `frame` counter rather than taken once per call, so a
+/// rollback-netcode entity (e.g. driven by GGRS) can resimulate the same frame more than
+/// once without the trail smearing: [`PolyLineTrail::push_at`] overwrites the current frame's
+/// sample in place instead of appending a duplicate, and only advances the ring when given a
+/// frame later than the one it last recorded. Combined with [`PolyLineTrail::snapshot`] and
+/// [`PolyLineTrail::restore`], a whole trail's visual state can be saved and rewound right
+/// alongside the gameplay state it illustrates.
+#[derive(Debug, Clone)]
+pub struct PolyLineTrail<const N: usize> {
+    /// Physical storage, same ring convention as [`PolyLine::ring_start`]: logically read
+    /// starting at `head` and wrapping around once full.
+    points: Vec<Vec3>,
+    head: usize,
+    len: usize,
+    /// Physical index written by the most recent `push_at`, so
+    /// [`poly_line_trail_system`] knows exactly which slot to mirror without
+    /// re-scanning the whole buffer.
+    last_write: usize,
+    /// Physical slots written by `push_at` since [`poly_line_trail_system`] last drained
+    /// them. `push_at` can run more than once per system tick (e.g. a fixed-timestep
+    /// simulation catching up after a frame hitch), so the mirror has to flush every slot
+    /// touched since its last run, not just `last_write`.
+    pending_writes: Vec<usize>,
+    /// Simulation frame the most recent `push_at` recorded, or `0` if the trail is empty.
+    /// A repeated or earlier frame number overwrites `last_write` in place rather than
+    /// advancing the ring, which is what makes replaying a frame idempotent.
+    frame: u64,
+}
+
+impl<const N: usize> Default for PolyLineTrail<N> {
+    fn default() -> Self {
+        PolyLineTrail {
+            points: vec![Vec3::ZERO; N],
+            head: 0,
+            len: 0,
+            last_write: 0,
+            pending_writes: Vec::new(),
+            frame: 0,
+        }
+    }
+}
+
+impl<const N: usize> PolyLineTrail<N> {
+    /// Records `point` as the sample for `frame`, overwriting the oldest point once the
+    /// trail has reached its capacity of `N`.
+    ///
+    /// If `frame` is not strictly greater than the frame last recorded, `point` replaces
+    /// that frame's sample instead of appending a new one. This is what keeps a resimulated
+    /// rollback frame from leaving duplicate points behind: pushing frame `F` twice (e.g.
+    /// once during a misprediction and once during the corrected replay) leaves exactly one
+    /// sample for `F`.
+    pub fn push_at(&mut self, frame: u64, point: Vec3) {
+        if self.len > 0 && frame <= self.frame {
+            self.points[self.last_write] = point;
+            self.pending_writes.push(self.last_write);
+            return;
+        }
+
+        let write_at = if self.len < N { self.len } else { self.head };
+        self.points[write_at] = point;
+        self.last_write = write_at;
+        self.frame = frame;
+        self.pending_writes.push(write_at);
+        if self.len < N {
+            self.len += 1;
+        } else {
+            self.head = (self.head + 1) % N;
+        }
+    }
+
+    /// The number of points currently in the trail, `0..=N`.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The trail's fixed capacity, `N`.
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Captures the trail's ring-buffer state so it can be rewound later with
+    /// [`PolyLineTrail::restore`].
+    ///
+    /// Take one of these alongside a rollback-netcode save state; since it holds every
+    /// physical slot together with `head`, `len`, the last written slot and `frame`,
+    /// restoring it reproduces the trail exactly, including its position in the ring.
+    pub fn snapshot(&self) -> PolyLineTrailSnapshot<N> {
+        PolyLineTrailSnapshot {
+            points: self.points.clone(),
+            head: self.head,
+            len: self.len,
+            last_write: self.last_write,
+            frame: self.frame,
+        }
+    }
+
+    /// Overwrites this trail's state with a previously captured [`PolyLineTrailSnapshot`],
+    /// e.g. to rewind to an earlier confirmed frame before resimulating.
+    ///
+    /// A restore can move every point at once (a rollback can jump further than any single
+    /// `push_at` would), so this marks the whole logical window `0..len` pending rather
+    /// than just the slot `snapshot` last wrote, ensuring [`poly_line_trail_system`]
+    /// re-mirrors everything the jump actually changed.
+    pub fn restore(&mut self, snapshot: &PolyLineTrailSnapshot<N>) {
+        self.points.clone_from(&snapshot.points);
+        self.head = snapshot.head;
+        self.len = snapshot.len;
+        self.last_write = snapshot.last_write;
+        self.frame = snapshot.frame;
+        self.pending_writes = (0..self.len).collect();
+    }
+}
+
+/// A point-in-time copy of a [`PolyLineTrail`]'s ring-buffer state, produced by
+/// [`PolyLineTrail::snapshot`] and consumed by [`PolyLineTrail::restore`].
+#[derive(Debug, Clone)]
+pub struct PolyLineTrailSnapshot<const N: usize> {
+    points: Vec<Vec3>,
+    head: usize,
+    len: usize,
+    last_write: usize,
+    frame: u64,
+}
+
+/// Components required to draw a fixed-length [`PolyLineTrail`].
+#[derive(Bundle)]
+pub struct PolyLineTrailBundle<const N: usize> {
+    pub trail: PolyLineTrail<N>,
+    #[bundle]
+    pub poly_line: PolyLineBundle,
+}
+
+impl<const N: usize> Default for PolyLineTrailBundle<N> {
+    fn default() -> Self {
+        PolyLineTrailBundle {
+            trail: PolyLineTrail::default(),
+            poly_line: PolyLineBundle::default(),
+        }
+    }
+}
+
+/// Keeps each [`PolyLineTrail`]'s backing [`PolyLine`] in sync, in place.
+///
+/// While the trail is still filling up (`len() < N`), `poly_line.vertices` grows by one
+/// element per `push_at`, same as a plain `Vec::push`. Once the trail is full, every
+/// further `push_at` only overwrites the slots that changed and bumps `poly_line.ring_start`,
+/// so the cost of keeping a full trail's `PolyLine` up to date never grows with `N` and
+/// never reallocates.
+///
+/// This system's own run rate (whatever [`crate::AddPolyLineTrail::add_poly_line_trail`]
+/// was given) is independent of however often `push_at` gets called — a fixed-timestep
+/// simulation can catch up with more than one `push_at` per app frame — so every pending
+/// write recorded by [`PolyLineTrail::push_at`] since this system's last run is flushed,
+/// not just the most recent one. A `restore`d trail is handled the same way: `restore`
+/// marks its whole logical window pending, so the jump is fully re-mirrored in one pass.
+pub fn poly_line_trail_system<const N: usize>(
+    mut query: Query<(&mut PolyLineTrail<N>, &mut PolyLine), Changed<PolyLineTrail<N>>>,
+) {
+    for (mut trail, mut poly_line) in query.iter_mut() {
+        if poly_line.vertices.len() != trail.len {
+            poly_line.vertices.resize(trail.len, Vec3::ZERO);
+        }
+        let pending = std::mem::take(&mut trail.pending_writes);
+        for slot in pending {
+            poly_line.vertices[slot] = trail.points[slot];
+        }
+        poly_line.ring_start = trail.head;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two `push_at` calls landing between one run of [`poly_line_trail_system`] (a
+    /// fixed-timestep simulation catching up after a frame hitch) must both end up in
+    /// `pending_writes`, not just the most recent one.
+    #[test]
+    fn push_at_accumulates_every_write_since_last_drain() {
+        let mut trail = PolyLineTrail::<4>::default();
+
+        trail.push_at(1, Vec3::new(1.0, 0.0, 0.0));
+        trail.push_at(2, Vec3::new(2.0, 0.0, 0.0));
+
+        assert_eq!(trail.pending_writes, vec![0, 1]);
+        assert_eq!(trail.points[0], Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(trail.points[1], Vec3::new(2.0, 0.0, 0.0));
+    }
+
+    /// Replaying the same (or an older) frame overwrites `last_write` in place rather than
+    /// advancing the ring, but the overwrite must still be queued for mirroring, or a
+    /// corrected resimulation's point would never reach the rendered `PolyLine`.
+    #[test]
+    fn push_at_replaying_a_frame_still_queues_the_overwrite() {
+        let mut trail = PolyLineTrail::<4>::default();
+
+        trail.push_at(1, Vec3::new(1.0, 0.0, 0.0));
+        trail.pending_writes.clear();
+
+        trail.push_at(1, Vec3::new(9.0, 0.0, 0.0));
+
+        assert_eq!(trail.pending_writes, vec![0]);
+        assert_eq!(trail.points[0], Vec3::new(9.0, 0.0, 0.0));
+    }
+
+    /// A snapshot/restore round trip should reproduce the trail's state exactly, including
+    /// the ring's `head` after the trail has wrapped around.
+    #[test]
+    fn snapshot_restore_round_trips_full_state() {
+        let mut trail = PolyLineTrail::<3>::default();
+        trail.push_at(1, Vec3::new(1.0, 0.0, 0.0));
+        trail.push_at(2, Vec3::new(2.0, 0.0, 0.0));
+        trail.push_at(3, Vec3::new(3.0, 0.0, 0.0));
+        // The ring is now full; this push overwrites the oldest physical slot (0).
+        trail.push_at(4, Vec3::new(4.0, 0.0, 0.0));
+
+        let snapshot = trail.snapshot();
+
+        // Mutate the trail further so restoring is a real rewind, not a no-op.
+        trail.push_at(5, Vec3::new(5.0, 0.0, 0.0));
+        assert_ne!(trail.points, snapshot.points);
+
+        trail.restore(&snapshot);
+
+        assert_eq!(trail.points, snapshot.points);
+        assert_eq!(trail.head, snapshot.head);
+        assert_eq!(trail.len, snapshot.len);
+        assert_eq!(trail.last_write, snapshot.last_write);
+        assert_eq!(trail.frame, snapshot.frame);
+        // A restore can jump every point at once, so the whole logical window must be
+        // queued for re-mirroring rather than just the slot last written before the jump.
+        assert_eq!(trail.pending_writes, vec![0, 1, 2]);
+    }
+}